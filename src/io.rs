@@ -0,0 +1,101 @@
+use std::io::Read;
+use std::sync::Arc;
+use num_bigint::BigUint;
+use crate::safe_db::edge_db::{Edge, EdgeDB};
+use crate::types::{Address, U256};
+
+/// The parsed edge set of a loaded safes binary snapshot.
+pub struct ImportResult {
+    edges: Arc<EdgeDB>,
+}
+
+impl ImportResult {
+    pub fn edges(&self) -> &Arc<EdgeDB> {
+        &self.edges
+    }
+}
+
+const ADDRESS_FIELD_LEN: usize = 42; // "0x" + 40 hex chars
+const CAPACITY_FIELD_LEN: usize = 32; // a big-endian u256
+const RECORD_LEN: usize = ADDRESS_FIELD_LEN * 3 + CAPACITY_FIELD_LEN;
+
+/// Reads a safes binary snapshot from any `Read` source — a local file or
+/// a streamed HTTP response body — so an in-flight checksum can be
+/// verified without buffering the whole snapshot first. The layout is a
+/// little-endian `u64` record count followed by that many fixed-width
+/// records: three `0x`-prefixed hex addresses, then a capacity as 32
+/// big-endian bytes.
+pub fn import_from_safes_binary_reader<R: Read>(
+    mut reader: R,
+) -> Result<ImportResult, Box<dyn std::error::Error>> {
+    let mut count_bytes = [0u8; 8];
+    reader.read_exact(&mut count_bytes)?;
+    let count = u64::from_le_bytes(count_bytes) as usize;
+
+    let mut edges = Vec::with_capacity(count);
+    let mut record = [0u8; RECORD_LEN];
+    for _ in 0..count {
+        reader
+            .read_exact(&mut record)
+            .map_err(|_| "Truncated safes binary stream: fewer records than declared")?;
+
+        let from = Address::from(address_field(&record[0..42])?);
+        let to = Address::from(address_field(&record[42..84])?);
+        let token = Address::from(address_field(&record[84..126])?);
+        let capacity = U256::from_bigint_truncating(BigUint::from_bytes_be(&record[126..158]));
+
+        edges.push(Edge { from, to, token, capacity });
+    }
+
+    Ok(ImportResult { edges: Arc::new(EdgeDB::from_edges(edges)) })
+}
+
+fn address_field(bytes: &[u8]) -> Result<&str, Box<dyn std::error::Error>> {
+    std::str::from_utf8(bytes).map_err(|e| format!("Invalid address encoding: {}", e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_record(from: &str, to: &str, token: &str, capacity: u64) -> Vec<u8> {
+        let mut record = Vec::with_capacity(RECORD_LEN);
+        record.extend_from_slice(from.as_bytes());
+        record.extend_from_slice(to.as_bytes());
+        record.extend_from_slice(token.as_bytes());
+        let mut capacity_bytes = [0u8; CAPACITY_FIELD_LEN];
+        capacity_bytes[CAPACITY_FIELD_LEN - 8..].copy_from_slice(&capacity.to_be_bytes());
+        record.extend_from_slice(&capacity_bytes);
+        record
+    }
+
+    #[test]
+    fn parses_a_well_formed_snapshot() {
+        let from = "0x0000000000000000000000000000000000000001";
+        let to = "0x0000000000000000000000000000000000000002";
+        let token = "0x0000000000000000000000000000000000000003";
+
+        let mut data = 1u64.to_le_bytes().to_vec();
+        data.extend(encode_record(from, to, token, 100));
+
+        let result = import_from_safes_binary_reader(data.as_slice()).expect("should parse");
+        assert_eq!(result.edges().edge_count(), 1);
+        let edge = &result.edges().edges()[0];
+        assert_eq!(edge.from.to_checksummed_hex().to_lowercase(), from);
+        assert_eq!(edge.capacity, U256::from_bigint_truncating(BigUint::from(100u32)));
+    }
+
+    #[test]
+    fn rejects_a_snapshot_truncated_before_its_declared_record_count() {
+        let from = "0x0000000000000000000000000000000000000001";
+        let to = "0x0000000000000000000000000000000000000002";
+        let token = "0x0000000000000000000000000000000000000003";
+
+        // Declares 2 records but only carries the bytes for 1.
+        let mut data = 2u64.to_le_bytes().to_vec();
+        data.extend(encode_record(from, to, token, 100));
+
+        let result = import_from_safes_binary_reader(data.as_slice());
+        assert!(result.is_err(), "a stream missing declared records must be rejected");
+    }
+}
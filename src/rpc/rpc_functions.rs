@@ -1,17 +1,20 @@
 use std::error::Error;
 use std::ffi::CString;
 use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io::Read;
 use std::str::FromStr;
 use json::JsonValue;
 use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
 use crate::graph;
-use crate::io::{import_from_safes_binary};
+use crate::io::import_from_safes_binary_reader;
 use crate::types::{Address, U256};
 use regex::Regex;
 use crate::rpc::call_context::CallContext;
 use lazy_static::lazy_static;
 use std::sync::{Arc, Mutex};
-use crate::safe_db::edge_db_dispenser::EdgeDbDispenser;
+use crate::safe_db::edge_db_dispenser::{EdgeDbDispenser, EdgeUpdate};
 use json::parse as json_parse;
 use std::os::raw::c_char;
 
@@ -26,19 +29,118 @@ pub struct JsonRpcRequest {
     pub params: JsonValue,
 }
 
-struct InputValidationError(String);
+/// Standard JSON-RPC 2.0 error codes, plus the pathfinder-specific range
+/// (-32000..-32099) reserved for server errors.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const INVALID_PARAMS: i32 = -32602;
+const NO_EDGES_LOADED: i32 = -32000;
+const NO_PATH_FOUND: i32 = -32001;
+const STALE_SEQUENCE: i32 = -32002;
 
-impl Error for InputValidationError {}
+/// A JSON-RPC 2.0 compliant error object: `{ code, message, data? }`.
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    pub data: Option<JsonValue>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: String) -> Self {
+        RpcError { code, message, data: None }
+    }
+
+    fn invalid_params(message: String) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    fn no_edges_loaded() -> Self {
+        Self::new(NO_EDGES_LOADED, "No edges loaded yet".to_string())
+    }
+
+    fn no_path_found(message: String) -> Self {
+        Self::new(NO_PATH_FOUND, message)
+    }
 
-impl Debug for InputValidationError {
+    fn stale_sequence(message: String) -> Self {
+        Self::new(STALE_SEQUENCE, message)
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let mut error = json::object! {
+            code: self.code,
+            message: self.message.clone(),
+        };
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+impl Error for RpcError {}
+
+impl Debug for RpcError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error: {}", self.0)
+        write!(f, "RpcError {{ code: {}, message: {} }}", self.code, self.message)
     }
 }
 
-impl Display for InputValidationError {
+impl Display for RpcError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error: {}", self.0)
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+/// Reads a `*const c_char` across the FFI boundary, so a null or
+/// non-UTF-8 pointer from a caller becomes a `-32700` error instead of
+/// unwinding (or, for a null pointer, reading invalid memory) inside
+/// `extern "C"`.
+fn read_c_str<'a>(ptr: *const c_char) -> Result<&'a str, RpcError> {
+    if ptr.is_null() {
+        return Err(RpcError::new(PARSE_ERROR, "Parse error: null request pointer".to_string()));
+    }
+    unsafe { std::ffi::CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|e| RpcError::new(PARSE_ERROR, format!("Parse error: invalid UTF-8 ({})", e)))
+}
+
+/// Parses a raw JSON-RPC request body, so a malformed payload from across
+/// the FFI boundary becomes a `-32700`/`-32600` error envelope instead of
+/// a panic. `id` is `JsonValue::Null` when parsing failed before an id
+/// could even be extracted.
+fn parse_json_rpc_request(request_str: &str) -> Result<JsonRpcRequest, RpcError> {
+    let parsed_json = json_parse(request_str)
+        .map_err(|e| RpcError::new(PARSE_ERROR, format!("Parse error: {}", e)))?;
+
+    if !parsed_json.is_object() {
+        return Err(RpcError::new(
+            INVALID_REQUEST,
+            "Invalid Request: expected a JSON object".to_string(),
+        ));
+    }
+
+    Ok(JsonRpcRequest {
+        id: parsed_json["id"].clone(),
+        method: parsed_json["method"].as_str().unwrap_or_default().to_string(),
+        params: parsed_json["params"].clone(),
+    })
+}
+
+/// Wraps a `compute_transfer` outcome in a full JSON-RPC 2.0 envelope,
+/// echoing back the request id on both success and failure.
+fn to_json_rpc_envelope(id: &JsonValue, result: Result<JsonValue, RpcError>) -> JsonValue {
+    match result {
+        Ok(result) => json::object! {
+            jsonrpc: "2.0",
+            id: id.clone(),
+            result: result,
+        },
+        Err(err) => json::object! {
+            jsonrpc: "2.0",
+            id: id.clone(),
+            error: err.to_json(),
+        },
     }
 }
 
@@ -49,34 +151,196 @@ pub extern "C" fn ffi_initialize() {
 }
 
 
+/// `source` is either a local file path or an `http(s)://` URL, in which
+/// case the snapshot is streamed straight off the network. `expected_hash`
+/// is optional (pass a null pointer to skip verification): a lower-case
+/// hex-encoded SHA-256 digest of the snapshot the caller expects to load.
+/// On mismatch, or any other load failure, this returns `usize::MAX` as a
+/// sentinel and leaves the previously published `EdgeDbDispenser` version
+/// untouched.
 #[no_mangle]
-pub extern "C" fn ffi_load_safes_binary(file: *const c_char) -> usize {
-    let file_str = unsafe { std::ffi::CStr::from_ptr(file).to_str().unwrap() };
+pub extern "C" fn ffi_load_safes_binary(source: *const c_char, expected_hash: *const c_char) -> usize {
+    let source_str = match read_c_str(source) {
+        Ok(s) => s,
+        Err(_) => return usize::MAX,
+    };
+    let expected_hash = if expected_hash.is_null() {
+        None
+    } else {
+        match read_c_str(expected_hash) {
+            Ok(s) => Some(s),
+            Err(_) => return usize::MAX,
+        }
+    };
     let dispenser = EDGE_DB_DISPENSER.lock().unwrap().as_ref().unwrap().clone();
     let call_context = CallContext::new(&dispenser); // Unwrap the parsed JSON
-    let result = load_safes_binary(file_str, &call_context).unwrap_or(0);
 
-    result
+    match load_safes_binary(source_str, expected_hash, &call_context) {
+        Ok(len) => len,
+        Err(_) => usize::MAX,
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn ffi_compute_transfer(request_json: *const c_char) -> *mut c_char {
-    let request_str = unsafe { std::ffi::CStr::from_ptr(request_json).to_str().unwrap() };
-    let parsed_json = json_parse(request_str).unwrap();
-    let request = JsonRpcRequest {
-        id: parsed_json["id"].clone(),
-        method: parsed_json["method"].as_str().unwrap_or_default().to_string(),
-        params: parsed_json["params"].clone(),
+    let request = match read_c_str(request_json).and_then(parse_json_rpc_request) {
+        Ok(request) => request,
+        Err(e) => {
+            let envelope = to_json_rpc_envelope(&JsonValue::Null, Err(e));
+            return CString::new(envelope.dump()).unwrap().into_raw();
+        }
     };
     let dispenser = EDGE_DB_DISPENSER.lock().unwrap().as_ref().unwrap().clone();
     let call_context = CallContext::new(&dispenser);
-    let result = compute_transfer(&request, &call_context).unwrap_or(json::object! {});
-    let c_string = CString::new(result.dump()).unwrap();
+    let result = compute_transfer(&request, &call_context);
+    let envelope = to_json_rpc_envelope(&request.id, result);
+    let c_string = CString::new(envelope.dump()).unwrap();
     c_string.into_raw()
 }
 
-pub fn load_safes_binary(file: &str, call_context: &CallContext) -> Result<usize, Box<dyn Error>> {
-    let updated_edges = import_from_safes_binary(file)?.edges().clone();
+/// Streaming variant of [`ffi_compute_transfer`] for `params.iterative`
+/// requests: `callback` is invoked once per iteration (`Some(1)`,
+/// `Some(2)`, then unbounded) with a full JSON-RPC envelope, so a caller
+/// can show a cheap short-path answer immediately and refine it as
+/// deeper searches complete, instead of waiting for the single unbounded
+/// result that `ffi_compute_transfer` returns.
+#[no_mangle]
+pub extern "C" fn ffi_compute_transfer_streaming(
+    request_json: *const c_char,
+    callback: extern "C" fn(*const c_char),
+) {
+    let request = match read_c_str(request_json).and_then(parse_json_rpc_request) {
+        Ok(request) => request,
+        Err(e) => {
+            let envelope = to_json_rpc_envelope(&JsonValue::Null, Err(e));
+            if let Ok(c_string) = CString::new(envelope.dump()) {
+                callback(c_string.as_ptr());
+            }
+            return;
+        }
+    };
+    let dispenser = EDGE_DB_DISPENSER.lock().unwrap().as_ref().unwrap().clone();
+    let call_context = CallContext::new(&dispenser);
+    let id = request.id.clone();
+
+    let emit = |payload: Result<JsonValue, RpcError>| {
+        let envelope = to_json_rpc_envelope(&id, payload);
+        if let Ok(c_string) = CString::new(envelope.dump()) {
+            callback(c_string.as_ptr());
+        }
+    };
+
+    let final_result =
+        compute_transfer_with_partials(&request, &call_context, |partial| emit(Ok(partial)));
+    emit(final_result);
+}
+
+/// Applies a batch of ordered graph events to the live edge set without
+/// re-reading a full safes binary snapshot. See [`apply_edge_updates`].
+#[no_mangle]
+pub extern "C" fn ffi_apply_edge_updates(request_json: *const c_char) -> *mut c_char {
+    let request = match read_c_str(request_json).and_then(parse_json_rpc_request) {
+        Ok(request) => request,
+        Err(e) => {
+            let envelope = to_json_rpc_envelope(&JsonValue::Null, Err(e));
+            return CString::new(envelope.dump()).unwrap().into_raw();
+        }
+    };
+    let dispenser = EDGE_DB_DISPENSER.lock().unwrap().as_ref().unwrap().clone();
+    let call_context = CallContext::new(&dispenser);
+    let result = apply_edge_updates(&request, &call_context);
+    let envelope = to_json_rpc_envelope(&request.id, result);
+    let c_string = CString::new(envelope.dump()).unwrap();
+    c_string.into_raw()
+}
+
+/// A digest mismatch between an `expected_hash` the caller supplied and
+/// the SHA-256 of the bytes actually read from a safes binary.
+struct ChecksumMismatchError {
+    expected: String,
+    actual: String,
+}
+
+impl Error for ChecksumMismatchError {}
+
+impl Debug for ChecksumMismatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Checksum mismatch: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl Display for ChecksumMismatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Checksum mismatch: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+/// Wraps a reader and hashes every byte as it is consumed, so a snapshot's
+/// digest can be verified in-flight instead of with a second pass over
+/// the file.
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        HashingReader { inner, hasher: Sha256::new() }
+    }
+
+    fn digest_hex(&self) -> String {
+        hex::encode(self.hasher.clone().finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Loads a safes binary snapshot either from a local file path or, when
+/// `source` is an `http(s)://` URL, by streaming the response body
+/// straight into the importer without buffering it to disk first.
+pub fn load_safes_binary(
+    source: &str,
+    expected_hash: Option<&str>,
+    call_context: &CallContext,
+) -> Result<usize, Box<dyn Error>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let response = reqwest::blocking::get(source)?.error_for_status()?;
+        load_safes_binary_from_reader(response, expected_hash, call_context)
+    } else {
+        let file = File::open(source)?;
+        load_safes_binary_from_reader(file, expected_hash, call_context)
+    }
+}
+
+fn load_safes_binary_from_reader<R: Read>(
+    reader: R,
+    expected_hash: Option<&str>,
+    call_context: &CallContext,
+) -> Result<usize, Box<dyn Error>> {
+    let mut hashing_reader = HashingReader::new(reader);
+    let updated_edges = import_from_safes_binary_reader(&mut hashing_reader)?.edges().clone();
+
+    // The importer may stop short of EOF (e.g. trailing padding after the
+    // last record). Drain whatever is left so the digest always covers
+    // the whole file/download, not just the bytes the importer consumed.
+    std::io::copy(&mut hashing_reader, &mut std::io::sink())?;
+
+    if let Some(expected) = expected_hash {
+        let actual = hashing_reader.digest_hex();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(Box::new(ChecksumMismatchError {
+                expected: expected.to_string(),
+                actual,
+            }));
+        }
+    }
+
     let len = updated_edges.edge_count();
 
     call_context.dispenser.update(updated_edges);
@@ -86,12 +350,24 @@ pub fn load_safes_binary(file: &str, call_context: &CallContext) -> Result<usize
 pub fn compute_transfer(
     request: &JsonRpcRequest,
     call_context: &CallContext,
-) -> Result<JsonValue, Box<dyn Error>> {
+) -> Result<JsonValue, RpcError> {
+    compute_transfer_with_partials(request, call_context, |_partial| {})
+}
+
+/// Core of [`compute_transfer`]. When `params.iterative` is set, runs
+/// `graph::compute_flow` successively with `max_distance` limits of
+/// `Some(1)`, `Some(2)`, then `None`, handing every intermediate result
+/// (`final: false`) to `on_partial` before returning the unbounded final
+/// one (`final: true`). When `params.iterative` is unset, this runs a
+/// single unbounded iteration and `on_partial` is never called.
+pub fn compute_transfer_with_partials<F: FnMut(JsonValue)>(
+    request: &JsonRpcRequest,
+    call_context: &CallContext,
+    mut on_partial: F,
+) -> Result<JsonValue, RpcError> {
     call_context.log_message(format!("{}", request.params).as_str());
     if call_context.version.is_none() {
-        return Err(Box::new(InputValidationError(
-            "No edges loaded yet".to_string(),
-        )));
+        return Err(RpcError::no_edges_loaded());
     }
 
     let edges = &call_context.version.as_ref().unwrap().edges;
@@ -111,8 +387,11 @@ pub fn compute_transfer(
     };
 
     let max_transfers = request.params["max_transfers"].as_u64();
+    let last_index = max_distances.len() - 1;
 
-    for max_distance in max_distances {
+    let mut final_result = None;
+
+    for (i, max_distance) in max_distances.into_iter().enumerate() {
         let (flow, transfers) = graph::compute_flow(
             &from_address,
             &to_address,
@@ -125,52 +404,190 @@ pub fn compute_transfer(
 
         call_context.log_message(&format!("Computed flow with max distance {:?}: {}", max_distance, flow));
 
-        // TODO: This implementation doesn't support the iterative approach anymore. Re-implement it.
-        return Ok(json::object! {
-                        maxFlowValue: flow.to_decimal(),
-                        final: max_distance.is_none(),
-                        transferSteps: transfers.into_iter().map(|e| json::object! {
-                            from: e.from.to_checksummed_hex(),
-                            to: e.to.to_checksummed_hex(),
-                            token_owner: e.token.to_checksummed_hex(),
-                            value: e.capacity.to_decimal(),
-                        }).collect::<Vec<_>>(),
-                    });
+        let is_final = i == last_index;
+        let has_path = !transfers.is_empty();
+        let payload = json::object! {
+            maxFlowValue: flow.to_decimal(),
+            final: is_final,
+            transferSteps: transfers.into_iter().map(|e| json::object! {
+                from: e.from.to_checksummed_hex(),
+                to: e.to.to_checksummed_hex(),
+                token_owner: e.token.to_checksummed_hex(),
+                value: e.capacity.to_decimal(),
+            }).collect::<Vec<_>>(),
+        };
+
+        if is_final {
+            final_result = Some(if has_path {
+                Ok(payload)
+            } else {
+                Err(RpcError::no_path_found(format!(
+                    "Couldn't find a path for {} CRC between {} -> {}.",
+                    parsed_value_param, from_address, to_address
+                )))
+            });
+        } else {
+            on_partial(payload);
+        }
     }
 
-    Err(Box::new(InputValidationError(format!(
-        "Couldn't find a path for {} CRC between {} -> {}.",
-        parsed_value_param, from_address, to_address
-    ))))
+    final_result.expect("max_distances is always non-empty")
 }
 
-fn validate_and_parse_u256(value_str: &str) -> Result<U256, Box<dyn Error>> {
+/// An ordered batch of [`EdgeUpdate`]s, tagged with the sequence/block
+/// number it was produced at so the dispenser can reject out-of-order or
+/// replayed batches.
+pub struct EdgeUpdateBatch {
+    pub sequence: u64,
+    pub updates: Vec<EdgeUpdate>,
+}
+
+/// Applies an [`EdgeUpdateBatch`] to the live edge set and atomically
+/// publishes the resulting version. The baseline safes binary load
+/// remains the bootstrap; deltas advance the graph from there.
+pub fn apply_edge_updates(
+    request: &JsonRpcRequest,
+    call_context: &CallContext,
+) -> Result<JsonValue, RpcError> {
+    let batch = parse_edge_update_batch(&request.params)?;
+    if call_context.version.is_none() {
+        return Err(RpcError::no_edges_loaded());
+    }
+    let applied = call_context
+        .dispenser
+        .apply_updates(batch.sequence, batch.updates)
+        .map_err(|e| RpcError::stale_sequence(format!("{}", e)))?;
+
+    call_context.log_message(&format!(
+        "Applied {} edge update(s) at sequence {}",
+        applied, batch.sequence
+    ));
+
+    Ok(json::object! {
+        sequence: batch.sequence,
+        applied: applied,
+    })
+}
+
+fn parse_edge_update_batch(params: &JsonValue) -> Result<EdgeUpdateBatch, RpcError> {
+    let sequence = params["sequence"]
+        .as_u64()
+        .ok_or_else(|| RpcError::invalid_params("Missing or invalid \"sequence\".".to_string()))?;
+
+    if !params["updates"].is_array() {
+        return Err(RpcError::invalid_params(
+            "\"updates\" must be an array.".to_string(),
+        ));
+    }
+
+    let updates = params["updates"]
+        .members()
+        .map(parse_edge_update)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(EdgeUpdateBatch { sequence, updates })
+}
+
+fn parse_edge_update(update: &JsonValue) -> Result<EdgeUpdate, RpcError> {
+    match update["type"].as_str() {
+        Some("TrustEdgeAdded") => Ok(EdgeUpdate::TrustEdgeAdded {
+            truster: validate_and_parse_ethereum_address(&update["truster"].to_string())?,
+            trustee: validate_and_parse_ethereum_address(&update["trustee"].to_string())?,
+            limit: validate_and_parse_u256(update["limit"].as_str().unwrap_or_default())?,
+        }),
+        Some("TrustEdgeRemoved") => Ok(EdgeUpdate::TrustEdgeRemoved {
+            truster: validate_and_parse_ethereum_address(&update["truster"].to_string())?,
+            trustee: validate_and_parse_ethereum_address(&update["trustee"].to_string())?,
+        }),
+        Some("CapacityChanged") => Ok(EdgeUpdate::CapacityChanged {
+            token_owner: validate_and_parse_ethereum_address(&update["token_owner"].to_string())?,
+            holder: validate_and_parse_ethereum_address(&update["holder"].to_string())?,
+            capacity: validate_and_parse_u256(update["capacity"].as_str().unwrap_or_default())?,
+        }),
+        Some("Signup") => Ok(EdgeUpdate::Signup {
+            safe: validate_and_parse_ethereum_address(&update["safe"].to_string())?,
+            token: validate_and_parse_ethereum_address(&update["token"].to_string())?,
+        }),
+        Some(other) => Err(RpcError::invalid_params(format!(
+            "Unknown edge update type: {}",
+            other
+        ))),
+        None => Err(RpcError::invalid_params(
+            "Missing \"type\" on edge update.".to_string(),
+        )),
+    }
+}
+
+fn validate_and_parse_u256(value_str: &str) -> Result<U256, RpcError> {
     match BigUint::from_str(value_str) {
         Ok(parsed_value) => {
             if parsed_value > U256::MAX.into() {
-                Err(Box::new(InputValidationError(format!(
+                Err(RpcError::invalid_params(format!(
                     "Value {} is too large. Maximum value is {}.",
                     parsed_value, U256::MAX
-                ))))
+                )))
             } else {
                 Ok(U256::from_bigint_truncating(parsed_value))
             }
         }
-        Err(e) => Err(Box::new(InputValidationError(format!(
+        Err(e) => Err(RpcError::invalid_params(format!(
             "Invalid value: {}. Couldn't parse value: {}",
             value_str, e
-        )))),
+        ))),
     }
 }
 
-fn validate_and_parse_ethereum_address(address: &str) -> Result<Address, Box<dyn Error>> {
+fn validate_and_parse_ethereum_address(address: &str) -> Result<Address, RpcError> {
     let re = Regex::new(r"^0x[0-9a-fA-F]{40}$").unwrap();
     if re.is_match(address) {
         Ok(Address::from(address))
     } else {
-        Err(Box::new(InputValidationError(format!(
+        Err(RpcError::invalid_params(format!(
             "Invalid Ethereum address: {}",
             address
-        ))))
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_c_str_rejects_a_null_pointer_instead_of_dereferencing_it() {
+        assert!(read_c_str(std::ptr::null()).is_err());
+    }
+
+    #[test]
+    fn read_c_str_rejects_invalid_utf8_instead_of_panicking() {
+        let bytes: [u8; 2] = [0xff, 0];
+        let ptr = bytes.as_ptr() as *const c_char;
+        assert!(read_c_str(ptr).is_err());
+    }
+
+    #[test]
+    fn hashing_reader_digest_matches_a_fully_drained_stream() {
+        let data = b"hello safes binary snapshot";
+        let mut hashing_reader = HashingReader::new(&data[..]);
+        let mut buf = Vec::new();
+        hashing_reader.read_to_end(&mut buf).unwrap();
+
+        let expected = hex::encode(Sha256::digest(data));
+        assert_eq!(hashing_reader.digest_hex(), expected);
+    }
+
+    #[test]
+    fn hashing_reader_digest_over_a_partial_read_does_not_match_the_full_stream() {
+        let data = b"hello safes binary snapshot plus trailing padding";
+        let mut hashing_reader = HashingReader::new(&data[..]);
+        let mut partial = [0u8; 5];
+        hashing_reader.read_exact(&mut partial).unwrap();
+
+        let partial_digest = hashing_reader.digest_hex();
+        let full_digest = hex::encode(Sha256::digest(data));
+        assert_ne!(
+            partial_digest, full_digest,
+            "a truncated read must not produce the same digest as the full stream"
+        );
     }
 }
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use crate::types::{Address, U256};
+
+/// A single directed edge in the live graph: up to `capacity` of `token`
+/// can flow from `from` to `to`.
+#[derive(Clone)]
+pub struct Edge {
+    pub from: Address,
+    pub to: Address,
+    pub token: Address,
+    pub capacity: U256,
+}
+
+type EdgeKey = (Address, Address, Address);
+
+/// The live edge set backing flow computation. Replaced wholesale on a
+/// full safes binary reload, or mutated incrementally by
+/// `EdgeDbDispenser::apply_updates`. Indexed by `(from, to, token)` so
+/// both paths can look up or update a single edge in O(1) instead of
+/// scanning the whole set, which matters once this is loaded from a
+/// multi-hundred-megabyte snapshot.
+#[derive(Clone, Default)]
+pub struct EdgeDB {
+    edges: Vec<Edge>,
+    index: HashMap<EdgeKey, usize>,
+}
+
+impl EdgeDB {
+    pub fn new() -> Self {
+        EdgeDB { edges: Vec::new(), index: HashMap::new() }
+    }
+
+    /// Builds an `EdgeDB` directly from already-deduplicated edges, e.g.
+    /// records freshly parsed off a safes binary snapshot, without paying
+    /// for a `set_capacity` lookup per edge.
+    pub fn from_edges(edges: Vec<Edge>) -> Self {
+        let mut index = HashMap::with_capacity(edges.len());
+        for (i, edge) in edges.iter().enumerate() {
+            index.insert(key_of(edge), i);
+        }
+        EdgeDB { edges, index }
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Inserts the `(from, to, token)` edge, or updates its capacity if it
+    /// already exists.
+    pub fn set_capacity(&mut self, from: Address, to: Address, token: Address, capacity: U256) {
+        let key = (from.clone(), to.clone(), token.clone());
+        if let Some(&i) = self.index.get(&key) {
+            self.edges[i].capacity = capacity;
+        } else {
+            self.index.insert(key, self.edges.len());
+            self.edges.push(Edge { from, to, token, capacity });
+        }
+    }
+
+    /// Removes the `(from, to, token)` edge, if present.
+    pub fn remove_edge(&mut self, from: &Address, to: &Address, token: &Address) {
+        let key = (from.clone(), to.clone(), token.clone());
+        if let Some(i) = self.index.remove(&key) {
+            self.edges.swap_remove(i);
+            if let Some(moved) = self.edges.get(i) {
+                self.index.insert(key_of(moved), i);
+            }
+        }
+    }
+}
+
+fn key_of(edge: &Edge) -> EdgeKey {
+    (edge.from.clone(), edge.to.clone(), edge.token.clone())
+}
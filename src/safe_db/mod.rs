@@ -0,0 +1,2 @@
+pub mod edge_db;
+pub mod edge_db_dispenser;